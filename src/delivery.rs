@@ -1,7 +1,199 @@
-use crate::{AmqpProperties, Channel, Result};
+use crate::io_loop::Channel0Handle;
+use crate::{AmqpProperties, Channel, ConsumerOptions, ErrorKind, FieldTable, Result};
 use amq_protocol::protocol::basic::{Deliver, GetOk};
+use amq_protocol::types::AMQPValue;
 
-/// A message delivered to a consumer.
+/// A lightweight, cloneable, [`Send`](https://doc.rust-lang.org/std/marker/trait.Send.html)
+/// handle that can acknowledge, reject, or nack the [`Delivery`](struct.Delivery.html) it was
+/// obtained from without needing a reference to the [`Channel`](struct.Channel.html) for every
+/// call. Obtain one via [`Delivery::acker`](struct.Delivery.html#method.acker) before handing the
+/// delivery's content off to, e.g., a pool of worker threads that don't otherwise have access to
+/// the consuming `Channel`.
+#[derive(Clone, Debug)]
+pub struct Acker {
+    channel0: Channel0Handle,
+    channel_id: u16,
+    delivery_tag: u64,
+}
+
+impl Acker {
+    fn new(channel0: Channel0Handle, channel_id: u16, delivery_tag: u64) -> Acker {
+        Acker {
+            channel0,
+            channel_id,
+            delivery_tag,
+        }
+    }
+
+    /// Acknowledge the delivery this acker was obtained from. If `multiple` is true, also acks
+    /// every other delivery received on the same channel with a smaller delivery tag.
+    pub fn ack(&self, multiple: bool) -> Result<()> {
+        self.channel0.basic_ack(self.channel_id, self.delivery_tag, multiple)
+    }
+
+    /// Nack the delivery this acker was obtained from. If `multiple` is true, also nacks every
+    /// other delivery received on the same channel with a smaller delivery tag. If `requeue` is
+    /// true, instructs the server to attempt to requeue the message(s).
+    pub fn nack(&self, multiple: bool, requeue: bool) -> Result<()> {
+        self.channel0
+            .basic_nack(self.channel_id, self.delivery_tag, multiple, requeue)
+    }
+
+    /// Reject the delivery this acker was obtained from. If `requeue` is true, instructs the
+    /// server to attempt to requeue the message.
+    pub fn reject(&self, requeue: bool) -> Result<()> {
+        self.channel0
+            .basic_reject(self.channel_id, self.delivery_tag, requeue)
+    }
+}
+
+/// Specifies where a consumer attached to a RabbitMQ stream queue should start reading from, via
+/// the `x-stream-offset` consumer argument. Stream queues (unlike regular queues) retain their
+/// full history and never forget acknowledged messages, so consumers must say where in that
+/// history they want to begin; see [`Delivery::stream_offset`](struct.Delivery.html#method.stream_offset)
+/// for checkpointing progress so a later consumer can resume where this one left off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamOffset {
+    /// Start from the first message still retained by the stream.
+    First,
+
+    /// Start from the most recently published message.
+    Last,
+
+    /// Start from the next message published after the consumer is registered.
+    Next,
+
+    /// Start from the given absolute offset.
+    Offset(u64),
+
+    /// Start from the first message published at or after the given Unix timestamp (seconds).
+    Timestamp(i64),
+}
+
+impl StreamOffset {
+    /// Inserts the `x-stream-offset` entry this offset specifier represents into a consumer's
+    /// arguments table, using the long-long, timestamp, or long-string encoding RabbitMQ expects
+    /// for each variant.
+    pub(crate) fn apply_to_arguments(self, arguments: &mut FieldTable) {
+        let value = match self {
+            StreamOffset::First => AMQPValue::LongString("first".into()),
+            StreamOffset::Last => AMQPValue::LongString("last".into()),
+            StreamOffset::Next => AMQPValue::LongString("next".into()),
+            StreamOffset::Offset(offset) => AMQPValue::LongLongInt(offset as i64),
+            StreamOffset::Timestamp(timestamp) => AMQPValue::Timestamp(timestamp as u64),
+        };
+        arguments.insert("x-stream-offset".into(), value);
+    }
+}
+
+/// Options for consuming from a RabbitMQ stream queue. Streams require `basic_qos` to have been
+/// called on the channel with a non-zero prefetch count before the consumer is registered; use
+/// [`apply_to`](#method.apply_to) to merge this into the [`ConsumerOptions`](struct.ConsumerOptions.html)
+/// passed to [`Channel::basic_consume`](struct.Channel.html#method.basic_consume).
+#[derive(Clone, Copy, Debug)]
+pub struct StreamConsumeOptions {
+    /// Where in the stream's history the consumer should start reading from.
+    pub offset: StreamOffset,
+}
+
+impl Default for StreamConsumeOptions {
+    fn default() -> Self {
+        StreamConsumeOptions {
+            offset: StreamOffset::Next,
+        }
+    }
+}
+
+impl StreamConsumeOptions {
+    pub fn offset(self, offset: StreamOffset) -> Self {
+        StreamConsumeOptions { offset, ..self }
+    }
+
+    /// Builds the `x-stream-offset` consumer argument, rejecting `prefetch_count` of zero. This
+    /// only checks the literal passed in, not any channel's real QoS state -- prefer
+    /// [`apply_to`](#method.apply_to), which actually sets the channel's QoS itself instead of
+    /// trusting the caller to have already done so with the same value. Returns
+    /// [`ErrorKind::StreamConsumeRequiresQos`](enum.ErrorKind.html#variant.StreamConsumeRequiresQos)
+    /// if `prefetch_count` is zero.
+    pub fn into_arguments(self, prefetch_count: u16) -> Result<FieldTable> {
+        if prefetch_count == 0 {
+            return Err(ErrorKind::StreamConsumeRequiresQos.into());
+        }
+        let mut arguments = FieldTable::default();
+        self.offset.apply_to_arguments(&mut arguments);
+        Ok(arguments)
+    }
+
+    /// Sets `channel`'s QoS to `prefetch_count` via `basic_qos` (required to be non-zero for
+    /// stream consumers) and merges the resulting `x-stream-offset` argument into `options`,
+    /// returning the [`ConsumerOptions`](struct.ConsumerOptions.html) ready to pass to
+    /// [`Channel::basic_consume`](struct.Channel.html#method.basic_consume). Unlike
+    /// [`into_arguments`](#method.into_arguments), this ties the QoS the channel actually gets to
+    /// the value used to build the consume arguments, rather than trusting the caller to pass the
+    /// same number to both.
+    pub fn apply_to(
+        self,
+        channel: &Channel,
+        options: ConsumerOptions,
+        prefetch_count: u16,
+    ) -> Result<ConsumerOptions> {
+        let arguments = self.into_arguments(prefetch_count)?;
+        channel.basic_qos(prefetch_count, false)?;
+        Ok(options.arguments(arguments))
+    }
+}
+
+/// The persistence setting of a message, carried in the `delivery_mode` property. Matches the
+/// encoding used by the AMQP spec (and other clients, e.g. the Haskell `amqp` package): `1` for
+/// non-persistent, `2` for persistent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeliveryMode {
+    /// The message is not saved to disk and will be lost if the broker restarts.
+    NonPersistent = 1,
+
+    /// The message is saved to disk (assuming it is also published to a durable queue) and will
+    /// survive a broker restart.
+    Persistent = 2,
+}
+
+impl DeliveryMode {
+    /// Converts from the raw `delivery_mode` byte, returning `None` for any value other than `1`
+    /// or `2`.
+    pub fn from_u8(value: u8) -> Option<DeliveryMode> {
+        match value {
+            1 => Some(DeliveryMode::NonPersistent),
+            2 => Some(DeliveryMode::Persistent),
+            _ => None,
+        }
+    }
+
+    /// Converts to the raw `delivery_mode` byte used on the wire.
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+impl AmqpProperties {
+    /// Returns a copy of these properties with `delivery_mode` set to
+    /// [`DeliveryMode::Persistent`](enum.DeliveryMode.html#variant.Persistent); shorthand for
+    /// `with_delivery_mode(DeliveryMode::Persistent)`.
+    pub fn persistent(self) -> AmqpProperties {
+        self.with_delivery_mode(DeliveryMode::Persistent)
+    }
+
+    /// Returns a copy of these properties with `delivery_mode` set to the given mode, so
+    /// publishers can express durability without remembering whether `1` or `2` means persistent.
+    pub fn with_delivery_mode(self, delivery_mode: DeliveryMode) -> AmqpProperties {
+        AmqpProperties {
+            delivery_mode: Some(delivery_mode.to_u8()),
+            ..self
+        }
+    }
+}
+
+/// A message delivered to a consumer. [`acker`](#method.acker) takes the `Channel` it needs
+/// rather than the delivery carrying its own `Channel0Handle`, the same way
+/// [`ack`](#method.ack)/[`nack`](#method.nack)/[`reject`](#method.reject) already do.
 #[derive(Clone, Debug)]
 pub struct Delivery {
     channel_id: u16,
@@ -68,6 +260,44 @@ impl Delivery {
         self.delivery_tag
     }
 
+    /// The absolute offset of this delivery within a RabbitMQ stream, read from the
+    /// `x-stream-offset` header RabbitMQ attaches to stream deliveries. Returns `None` for
+    /// deliveries from ordinary (non-stream) queues. Stream consumers can record this value to
+    /// resume from the following offset (via [`StreamOffset::Offset`](enum.StreamOffset.html#variant.Offset))
+    /// after a restart.
+    pub fn stream_offset(&self) -> Option<u64> {
+        match self.properties.headers.as_ref()?.get("x-stream-offset")? {
+            AMQPValue::LongLongInt(offset) => Some(*offset as u64),
+            _ => None,
+        }
+    }
+
+    /// The persistence setting this message was published with, decoded from the `delivery_mode`
+    /// property. Returns `None` if the publisher did not set `delivery_mode`, or set it to a
+    /// value other than `1` or `2`.
+    pub fn delivery_mode(&self) -> Option<DeliveryMode> {
+        DeliveryMode::from_u8(self.properties.delivery_mode?)
+    }
+
+    /// Returns a cloneable [`Acker`](struct.Acker.html) that can ack, nack, or reject this
+    /// delivery without needing the [`Channel`](struct.Channel.html) it arrived on for every call,
+    /// e.g. from a worker thread that only owns the delivery's content. `channel` must be the same
+    /// channel this delivery was received on.
+    ///
+    /// # Panics
+    ///
+    /// This method will attempt to panic if `channel` does not match the channel this delivery was
+    /// received on. It does this by comparing channel IDs, so it is possible that an incorrect
+    /// `Delivery`/`Channel` pairing will not be detected at runtime.
+    pub fn acker(&self, channel: &Channel) -> Acker {
+        assert_eq!(
+            self.channel_id,
+            channel.channel_id(),
+            "cannot create acker for delivery on different channel"
+        );
+        Acker::new(channel.channel0_handle(), self.channel_id, self.delivery_tag)
+    }
+
     /// Acknowledge this delivery, which must have been received on the given channel. If
     /// `multiple` is true, acks this delivery and all other deliveries received on this channel
     /// with smaller [`delivery_tag`](#method.delivery_tag)s.
@@ -131,3 +361,66 @@ impl Delivery {
         channel.basic_reject(self, requeue)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_offset_encodes_expected_variants() {
+        let mut arguments = FieldTable::default();
+        StreamOffset::First.apply_to_arguments(&mut arguments);
+        assert_eq!(
+            arguments.get("x-stream-offset"),
+            Some(&AMQPValue::LongString("first".into()))
+        );
+
+        let mut arguments = FieldTable::default();
+        StreamOffset::Offset(42).apply_to_arguments(&mut arguments);
+        assert_eq!(
+            arguments.get("x-stream-offset"),
+            Some(&AMQPValue::LongLongInt(42))
+        );
+
+        let mut arguments = FieldTable::default();
+        StreamOffset::Timestamp(1_600_000_000).apply_to_arguments(&mut arguments);
+        assert_eq!(
+            arguments.get("x-stream-offset"),
+            Some(&AMQPValue::Timestamp(1_600_000_000))
+        );
+    }
+
+    #[test]
+    fn stream_consume_options_requires_nonzero_prefetch() {
+        let options = StreamConsumeOptions::default();
+        match options.into_arguments(0) {
+            Err(err) => assert_eq!(err.kind(), &ErrorKind::StreamConsumeRequiresQos),
+            Ok(_) => panic!("expected StreamConsumeRequiresQos"),
+        }
+    }
+
+    #[test]
+    fn stream_consume_options_builds_arguments_with_qos() {
+        let options = StreamConsumeOptions::default().offset(StreamOffset::Last);
+        let arguments = options.into_arguments(10).expect("qos is set");
+        assert_eq!(
+            arguments.get("x-stream-offset"),
+            Some(&AMQPValue::LongString("last".into()))
+        );
+    }
+
+    #[test]
+    fn delivery_mode_round_trips_through_u8() {
+        assert_eq!(DeliveryMode::NonPersistent.to_u8(), 1);
+        assert_eq!(DeliveryMode::Persistent.to_u8(), 2);
+        assert_eq!(DeliveryMode::from_u8(1), Some(DeliveryMode::NonPersistent));
+        assert_eq!(DeliveryMode::from_u8(2), Some(DeliveryMode::Persistent));
+    }
+
+    #[test]
+    fn delivery_mode_from_u8_rejects_unknown_values() {
+        assert_eq!(DeliveryMode::from_u8(0), None);
+        assert_eq!(DeliveryMode::from_u8(3), None);
+        assert_eq!(DeliveryMode::from_u8(255), None);
+    }
+}
@@ -159,6 +159,32 @@ pub enum ErrorKind {
     )]
     UnknownConsumerTag(u16, String),
 
+    /// The supplied AMQP URI could not be parsed.
+    #[fail(display = "invalid AMQP URI: {}", _0)]
+    InvalidUri(String),
+
+    /// Consuming from a RabbitMQ stream queue requires `basic_qos` to have been called on the
+    /// channel with a non-zero prefetch count before the consumer is registered.
+    #[fail(display = "stream consumers require a non-zero prefetch count set via basic_qos")]
+    StreamConsumeRequiresQos,
+
+    /// Automatic recovery (see
+    /// [`ConnectionTuning::recovery`](struct.ConnectionTuning.html#structfield.recovery)) gave up
+    /// after exhausting its configured reconnect attempts.
+    #[fail(display = "gave up recovering connection after {} attempt(s)", _0)]
+    RecoveryFailed(u32),
+
+    /// [`ConnectionTuning::recovery`](struct.ConnectionTuning.html#structfield.recovery) was set,
+    /// but the connection was opened via
+    /// [`Connection::open`](struct.Connection.html#method.open) or
+    /// [`Connection::open_tls`](struct.Connection.html#method.open_tls), neither of which can
+    /// reconnect (they take an already-connected stream with no way to produce a fresh one). Use
+    /// [`Connection::open_recoverable`](struct.Connection.html#method.open_recoverable) instead.
+    #[fail(
+        display = "ConnectionTuning::recovery requires Connection::open_recoverable; open/open_tls cannot reconnect"
+    )]
+    RecoveryRequiresOpenRecoverable,
+
     #[doc(hidden)]
     #[fail(display = "invalid error case")]
     __Nonexhaustive,